@@ -1,6 +1,8 @@
 use pyo3::pyclass;
 use pyo3::pymethods;
+use crate::Centroid;
 use crate::Vec2D;
+use crate::ops;
 use std::f64::consts::PI;
 
 /// Trait that allows standard evaluation of distortion functions 
@@ -93,6 +95,14 @@ impl BiVarPolyDistortions {
             ).collect()
         );
     }
+
+    /// fit coefficients to a set of measured centroids via regularized
+    /// least-squares, returning the per-axis residual RMS `(rms_x, rms_y)`.
+    /// Fails if there are fewer centroids than coefficients (or they're
+    /// otherwise degenerate) and `lambda` isn't large enough to compensate.
+    pub fn fit(&mut self, centroids: Vec<Centroid>, lambda: f64) -> crate::Result<(f64, f64)> {
+        crate::fit::fit_distortions(self, &centroids, lambda)
+    }
 }
 
 impl DistortionBasis for BiVarPolyDistortions {
@@ -103,7 +113,7 @@ impl DistortionBasis for BiVarPolyDistortions {
         y -= (self.shape[0] as f64)/2.0;
         x /= self.shape[1] as f64;
         y /= self.shape[0] as f64;
-        x.powf(k as f64)*y.powf((n-k) as f64)
+        ops::pow(x, k as f64)*ops::pow(y, (n-k) as f64)
     }
     
     fn get_coeffs(&self) -> &Vec<Vec2D> {
@@ -173,8 +183,171 @@ impl BiVarFourierDistortions {
             ).collect()
         );
     }
+
+    /// fit coefficients to a set of measured centroids via regularized
+    /// least-squares, returning the per-axis residual RMS `(rms_x, rms_y)`.
+    /// Fails if there are fewer centroids than coefficients (or they're
+    /// otherwise degenerate) and `lambda` isn't large enough to compensate.
+    pub fn fit(&mut self, centroids: Vec<Centroid>, lambda: f64) -> crate::Result<(f64, f64)> {
+        crate::fit::fit_distortions(self, &centroids, lambda)
+    }
 }
 
+/// Bivariate Zernike-Based functions to be used as distortion basis function
+///
+/// Unlike [`BiVarPolyDistortions`], which evaluates raw monomials that become
+/// severely ill-conditioned at high `degree`, this basis evaluates the
+/// (real-valued) Zernike polynomials, which are orthogonal over the unit
+/// disk. Modes are indexed by the standard
+/// [Noll ordering](https://en.wikipedia.org/wiki/Zernike_polynomials#Noll's_sequential_indices),
+/// skipping the piston term (`n=0,m=0`) so that index `0` corresponds to the
+/// first non-trivial mode, matching the convention used by
+/// [`BiVarPolyDistortions`].
+#[pyclass]
+pub struct BiVarZernikeDistortions{
+    /// maximum radial order (maximum `n` of any included mode)
+    pub degree: usize,
+    /// coefficients of distortions
+    pub coeffs: Vec<Vec2D>,
+    /// shape of image (numpy format)
+    pub shape: [usize; 2],
+    nm_lut: Vec<(usize,usize,bool)>,
+    factorial: Vec<f64>,
+}
+
+#[pymethods]
+impl BiVarZernikeDistortions {
+    /// construct a new set of bivariate Zernike polynomials, up to and
+    /// including radial order `degree`
+    #[new]
+    pub fn new(degree: usize, shape: [usize; 2]) -> Self {
+        let coeffs = vec![Vec2D{x:0.0,y:0.0}; ((degree+1)*(degree+2))/2-1];
+        let ncoeffs = coeffs.len();
+        let mut factorial = vec![1.0; degree+1];
+        for n in 1..=degree {
+            factorial[n] = factorial[n-1] * n as f64;
+        }
+        Self {
+            degree,
+            coeffs,
+            shape,
+            nm_lut: (0..ncoeffs).map(Self::noll_to_nm).collect(),
+            factorial,
+        }
+    }
+
+    #[staticmethod]
+    fn noll_to_nm(j: usize) -> (usize, usize, bool) {
+        // `j` is the 0-indexed position amongst non-piston modes, so the
+        // true (1-indexed) Noll index is j+2 (the piston mode, Noll index
+        // 1, is always skipped).
+        let noll = j + 2;
+        let mut n: usize = 0;
+        let mut rem = noll;
+        while rem > n+1 {
+            n += 1;
+            rem -= n;
+        }
+        let m = if n % 2 == 0 {
+            2*(rem/2)
+        } else {
+            2*((rem.saturating_sub(1))/2)+1
+        };
+        // even noll index -> cosine term, odd -> sine term (m=0 is always
+        // the plain radial polynomial, with no azimuthal dependence)
+        let is_cos = noll % 2 == 0;
+        (n, m, is_cos)
+    }
+
+    /// sample basis function given index at x/y coordinates
+    pub fn sample_xy(&self, x: f64, y: f64, ell: usize) -> f64 {
+        self.sample(&Vec2D{x,y}, ell)
+    }
+
+    /// evaluate distortions (including coefficients) at x/y coordinates
+    pub fn eval_xy(&self, x: f64, y: f64) -> (f64,f64) {
+        let Vec2D{x,y} = self.eval(&Vec2D{x,y});
+        (x,y)
+    }
+
+    #[getter]
+    fn ncoeffs(&self) -> usize {
+        self.coeffs.len()
+    }
+
+    #[getter]
+    fn coeffs(&self) -> Vec<Vec<f64>> {
+        self.coeffs.clone().into_iter()
+        .map(|v| {
+            let Vec2D{x,y} = v;
+            vec![x,y]
+        }).collect::<Vec<Vec<f64>>>()
+    }
+
+    /// load coefficients (e.g.) from python
+    pub fn load_coeffs(&mut self, coeffs: Vec<Vec<f64>>) {
+        self.set_coeffs(
+            coeffs.into_iter().map(|p|
+                Vec2D{x: p[0], y: p[1]}
+            ).collect()
+        );
+    }
+
+    /// fit coefficients to a set of measured centroids via regularized
+    /// least-squares, returning the per-axis residual RMS `(rms_x, rms_y)`.
+    /// Fails if there are fewer centroids than coefficients (or they're
+    /// otherwise degenerate) and `lambda` isn't large enough to compensate.
+    pub fn fit(&mut self, centroids: Vec<Centroid>, lambda: f64) -> crate::Result<(f64, f64)> {
+        crate::fit::fit_distortions(self, &centroids, lambda)
+    }
+}
+
+impl BiVarZernikeDistortions {
+    /// radial polynomial `R_n^m(rho)`
+    fn radial(&self, n: usize, m: usize, rho: f64) -> f64 {
+        let smax = (n-m)/2;
+        (0..=smax).map(|s| {
+            let num = self.factorial[n-s];
+            let den = self.factorial[s]
+                * self.factorial[(n+m)/2-s]
+                * self.factorial[(n-m)/2-s];
+            let sign = if s % 2 == 0 {1.0} else {-1.0};
+            sign * num / den * ops::pow(rho, (n-2*s) as f64)
+        }).sum()
+    }
+}
+
+impl DistortionBasis for BiVarZernikeDistortions {
+    fn sample(&self, pos: &Vec2D, index: usize) -> f64 {
+        let (n,m,is_cos) = self.nm_lut[index];
+        let Vec2D{x, y} = pos;
+        let cx = (self.shape[1] as f64)/2.0;
+        let cy = (self.shape[0] as f64)/2.0;
+        let half_diag = ops::sqrt(cx*cx+cy*cy);
+        let x = (x-cx)/half_diag;
+        let y = (y-cy)/half_diag;
+        let rho = ops::sqrt(x*x+y*y);
+        let theta = if rho == 0.0 { 0.0 } else { ops::atan2(y, x) };
+        let r = self.radial(n, m, rho);
+        if m == 0 {
+            r
+        } else if is_cos {
+            r*ops::cos(m as f64*theta)
+        } else {
+            r*ops::sin(m as f64*theta)
+        }
+    }
+
+    fn get_coeffs(&self) -> &Vec<Vec2D> {
+        &self.coeffs
+    }
+
+    fn set_coeffs(&mut self, coeffs: Vec<Vec2D>) {
+        self.coeffs = coeffs;
+    }
+}
+
+
 impl DistortionBasis for BiVarFourierDistortions {
     fn sample(&self, pos: &Vec2D, index: usize) -> f64 {
         let Vec2D{mut x, mut y} = pos;
@@ -185,10 +358,10 @@ impl DistortionBasis for BiVarFourierDistortions {
         let freq_x: f64 = 1.0 * PI * (((index / 4) / self.max_freq) % self.max_freq) as f64;
         let freq_y: f64 = 1.0 * PI * ((index / 4) % self.max_freq) as f64;
         match index % 4 {
-            0 => (freq_x*x).cos()*(freq_y*y).cos(),
-            1 => (freq_x*x).cos()*(freq_y*y).sin(),
-            2 => (freq_x*x).sin()*(freq_y*y).cos(),
-            3 => (freq_x*x).sin()*(freq_y*y).sin(),
+            0 => ops::cos(freq_x*x)*ops::cos(freq_y*y),
+            1 => ops::cos(freq_x*x)*ops::sin(freq_y*y),
+            2 => ops::sin(freq_x*x)*ops::cos(freq_y*y),
+            3 => ops::sin(freq_x*x)*ops::sin(freq_y*y),
             _ => unreachable!(),
         }
         
@@ -0,0 +1,82 @@
+//! Tolerant, pattern-driven batch loading of FITS images.
+//!
+//! Scanning a directory of calibration frames with [`crate::load_images`]
+//! aborts the whole run on the first corrupt or unreadable FITS file. This
+//! module instead walks a directory tree, selects files with a
+//! [`PatternSet`], and loads each one independently, collecting failures
+//! into a [`BadMatch`] report rather than bailing out, so a mostly-good
+//! dataset can still be calibrated.
+
+use std::path::{Path, PathBuf};
+
+use pyo3::{pyclass, pymethods};
+use rayon::prelude::*;
+
+use crate::patterns::PatternSet;
+use crate::{Image, Result};
+
+/// a file that matched the selection patterns but failed to load, along
+/// with why
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct BadMatch {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[pymethods]
+impl BadMatch {
+    /// path of the file that failed to load
+    #[getter]
+    fn path(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+    /// reason the file failed to load
+    #[getter]
+    fn reason(&self) -> String {
+        self.reason.clone()
+    }
+}
+
+/// recursively collect every file under `root` whose path (relative to
+/// `root`) matches `patterns`
+fn select_files(root: &Path, dir: &Path, patterns: &PatternSet, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            select_files(root, &path, patterns, out)?;
+        } else {
+            let rel = path.strip_prefix(root).unwrap_or(&path);
+            if patterns.matches(&rel.to_string_lossy()) {
+                out.push(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// walk `root`, select files matching `patterns` (see [`crate::patterns`]
+/// for the supported syntaxes), and load each as an [`Image`], continuing
+/// past any individual file that fails to load
+///
+/// Returns the successfully-loaded images alongside a [`BadMatch`] report
+/// for every selected file that could not be loaded.
+pub fn load_images_checked(root: &str, patterns: &[String]) -> Result<(Vec<Image>, Vec<BadMatch>)> {
+    let pattern_set = PatternSet::parse(patterns)?;
+    let mut paths = vec![];
+    select_files(Path::new(root), Path::new(root), &pattern_set, &mut paths)?;
+
+    let (images, bad): (Vec<_>, Vec<_>) = paths.into_par_iter()
+        .map(|path| match Image::from_fits(path.to_str().unwrap()) {
+            Ok(image) => Ok(image),
+            Err(e) => Err(BadMatch{path, reason: e.to_string()}),
+        })
+        .collect::<Vec<std::result::Result<Image, BadMatch>>>()
+        .into_iter()
+        .partition(std::result::Result::is_ok);
+
+    Ok((
+        images.into_iter().map(std::result::Result::unwrap).collect(),
+        bad.into_iter().map(std::result::Result::unwrap_err).collect(),
+    ))
+}
@@ -1,8 +1,21 @@
 use std::fmt;
+use std::path::{Path, PathBuf};
 
-use pyo3::{exceptions::PyValueError, PyErr};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyFileNotFoundError, PyOSError};
+use pyo3::PyErr;
 pub type Result<T> = std::result::Result<T, MavDACError>;
 
+// Python exception hierarchy, so callers can `except` on the specific
+// failure class instead of string-matching a generic `ValueError`.
+create_exception!(mavdac, MavdacError, PyException, "base class for all mavdac errors");
+create_exception!(mavdac, BadPatternError, MavdacError, "an image-selection pattern was malformed");
+create_exception!(mavdac, UnreadablePathError, MavdacError, "a path matched by a pattern could not be read");
+create_exception!(mavdac, InvalidFitsError, MavdacError, "a FITS file had a missing or invalid header keyword, or invalid data");
+create_exception!(mavdac, CoordinateError, MavdacError, "a coordinate was malformed or out of bounds");
+create_exception!(mavdac, ConfigError, MavdacError, "a YAML config file was malformed");
+create_exception!(mavdac, FitError, MavdacError, "a least-squares distortion fit could not be solved");
+
 /// error type for mavdac crate
 #[derive(Debug)]
 pub enum MavDACError {
@@ -13,11 +26,113 @@ pub enum MavDACError {
     /// io error wrapper
     IOError(std::io::Error),
     /// fits image file is invalid
-    InvalidFITS(String),
+    InvalidFITS(Diagnostic),
     /// invalid coordinate, e.g., out of bounds
     Coordinate(String),
     /// yaml file error
-    YAMLError(serde_yaml::Error),
+    YAMLError(Diagnostic),
+    /// image-selection pattern (see [`crate::patterns`]) failed to compile
+    Pattern(PatternError),
+    /// the normal-equations matrix of a [`crate::fit::fit_distortions`]
+    /// call was singular (e.g. fewer centroids than basis coefficients,
+    /// or duplicate/collinear centroids) and could not be Cholesky-factored
+    Fit(String),
+}
+
+/// a parse error with enough context (file, line/column, source snippet) to
+/// point a user at exactly what went wrong, rather than just an opaque
+/// message
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// the file the error originated from, if known
+    pub path: Option<PathBuf>,
+    /// 1-indexed line (or FITS header card index) the error originated
+    /// from, if known
+    pub line: Option<usize>,
+    /// 1-indexed column the error originated from, if known
+    pub column: Option<usize>,
+    /// a snippet of the offending source line/card, if available
+    pub snippet: Option<String>,
+    /// short, human-readable description of the problem
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>) -> Self {
+        Diagnostic{path: None, line: None, column: None, snippet: None, message: message.into()}
+    }
+    pub fn with_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+    pub fn with_location(mut self, line: usize, column: usize) -> Self {
+        self.line = Some(line);
+        self.column = Some(column);
+        self
+    }
+    pub fn with_snippet(mut self, snippet: impl Into<String>) -> Self {
+        self.snippet = Some(snippet.into());
+        self
+    }
+
+    /// a richer, multi-line rendering of this diagnostic: the file and
+    /// location, the offending snippet, and a caret pointing at the
+    /// problem column
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        if let Some(path) = &self.path {
+            out.push_str(&path.display().to_string());
+            if let Some(line) = self.line {
+                out.push_str(&format!(":{}", line));
+                if let Some(column) = self.column {
+                    out.push_str(&format!(":{}", column));
+                }
+            }
+            out.push_str(": ");
+        }
+        out.push_str(&self.message);
+        if let Some(snippet) = &self.snippet {
+            out.push('\n');
+            out.push_str(snippet);
+            if let Some(column) = self.column {
+                out.push('\n');
+                out.push_str(&" ".repeat(column.saturating_sub(1)));
+                out.push('^');
+            }
+        }
+        out
+    }
+}
+
+/// `Display` is deliberately terse (just the message) for CLI/Python error
+/// messages; use [`Diagnostic::report`] for a file/line/snippet rendering.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// an image-selection pattern that failed to compile, along with where in
+/// the pattern the problem was found
+#[derive(Debug)]
+pub struct PatternError {
+    /// the full pattern string (including its `glob:`/`re:`/etc. prefix)
+    /// that failed to compile
+    pub pattern: String,
+    /// best-effort byte offset of the offending span, as reported by the
+    /// underlying regex compiler against its *translated* input (glob
+    /// bodies are rewritten and anchors are inserted before compiling);
+    /// for `re:` patterns it is an offset into `pattern` past its `re:`
+    /// prefix, and for other syntaxes it does not index into `pattern`
+    pub offset: usize,
+    /// the underlying compiler's error message
+    pub message: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bad pattern '{}' at offset {}: {}", self.pattern, self.offset, self.message)
+    }
 }
 
 impl fmt::Display for MavDACError {
@@ -26,9 +141,49 @@ impl fmt::Display for MavDACError {
             MavDACError::BadPattern(..) => write!(f, "bad input pattern"),
             MavDACError::UnreadablePath(..) => write!(f, "unreadable path"),
             MavDACError::IOError(e) => write!(f, "{}", e),
-            MavDACError::InvalidFITS(s) => write!(f, "{}", s),
+            MavDACError::InvalidFITS(d) => write!(f, "{}", d),
             MavDACError::Coordinate(s) => write!(f, "{}", s),
-            MavDACError::YAMLError(e) => write!(f, "{}", e),
+            MavDACError::YAMLError(d) => write!(f, "{}", d),
+            MavDACError::Pattern(e) => write!(f, "{}", e),
+            MavDACError::Fit(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl MavDACError {
+    /// attach a file path to this error's diagnostic, if it carries one
+    pub fn with_path(mut self, path: impl AsRef<Path>) -> Self {
+        match &mut self {
+            MavDACError::InvalidFITS(d) | MavDACError::YAMLError(d) => {
+                d.path = Some(path.as_ref().to_path_buf());
+            },
+            _ => {},
+        }
+        self
+    }
+
+    /// attach a source-line snippet to a [`MavDACError::YAMLError`]'s
+    /// diagnostic, sliced out of `source` at the diagnostic's line, if it
+    /// knows which line it's on (other variants, including `InvalidFITS`,
+    /// are returned unchanged)
+    pub fn with_snippet(mut self, source: &str) -> Self {
+        if let MavDACError::YAMLError(d) = &mut self {
+            if let Some(line) = d.line {
+                if let Some(text) = source.lines().nth(line.saturating_sub(1)) {
+                    d.snippet = Some(text.trim_end().to_string());
+                }
+            }
+        }
+        self
+    }
+
+    /// a richer, multi-line rendering of this error: the file, line, and a
+    /// caret pointing at the offending span, where available
+    pub fn report(&self) -> String {
+        match self {
+            MavDACError::InvalidFITS(d) => d.report(),
+            MavDACError::YAMLError(d) => d.report(),
+            other => other.to_string(),
         }
     }
 }
@@ -41,7 +196,9 @@ impl std::error::Error for MavDACError {
             MavDACError::IOError(err) => Some(err),
             MavDACError::InvalidFITS(_) => Some(self),
             MavDACError::Coordinate(_) => Some(self),
-            MavDACError::YAMLError(err) => Some(err),
+            MavDACError::YAMLError(_) => Some(self),
+            MavDACError::Pattern(_) => Some(self),
+            MavDACError::Fit(_) => Some(self),
         }
     }
 }
@@ -67,18 +224,30 @@ impl From<std::io::Error> for MavDACError {
 impl From<MavDACError> for PyErr {
     fn from(value: MavDACError) -> Self {
         match value {
-            MavDACError::BadPattern(pattern_error) => PyValueError::new_err(pattern_error.to_string()),
-            MavDACError::UnreadablePath(glob_error) => PyValueError::new_err(glob_error.to_string()),
-            MavDACError::IOError(error) => PyValueError::new_err(error.to_string()),
-            MavDACError::InvalidFITS(s) => PyValueError::new_err(s),
-            MavDACError::Coordinate(s) => PyValueError::new_err(s),
-            MavDACError::YAMLError(error) => PyValueError::new_err(error.to_string()),
+            MavDACError::BadPattern(pattern_error) => BadPatternError::new_err(pattern_error.to_string()),
+            MavDACError::UnreadablePath(glob_error) => UnreadablePathError::new_err(glob_error.to_string()),
+            MavDACError::IOError(error) => match error.kind() {
+                std::io::ErrorKind::NotFound => PyFileNotFoundError::new_err(error.to_string()),
+                _ => PyOSError::new_err(error.to_string()),
+            },
+            MavDACError::InvalidFITS(d) => InvalidFitsError::new_err(d.report()),
+            MavDACError::Coordinate(s) => CoordinateError::new_err(s),
+            MavDACError::YAMLError(d) => ConfigError::new_err(d.report()),
+            // the new pattern subsystem's patterns are a refinement of the
+            // same "bad search pattern" problem as `glob::PatternError`
+            MavDACError::Pattern(e) => BadPatternError::new_err(e.to_string()),
+            MavDACError::Fit(s) => FitError::new_err(s),
         }
     }
 }
 
 impl From<serde_yaml::Error> for MavDACError {
     fn from(value: serde_yaml::Error) -> Self {
-        MavDACError::YAMLError(value)
+        let message = value.to_string();
+        let diagnostic = match value.location() {
+            Some(loc) => Diagnostic::new(message).with_location(loc.line(), loc.column()),
+            None => Diagnostic::new(message),
+        };
+        MavDACError::YAMLError(diagnostic)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,73 @@
+//! Native least-squares fitting of [`DistortionBasis`] coefficients.
+//!
+//! Previously, fitting distortion coefficients to measured centroids was
+//! left to python (`numpy.linalg`), since no Rust linalg crate was trusted
+//! for the job at the time. This module fits directly against the design
+//! matrix built from [`DistortionBasis::sample`], so a whole
+//! measure-then-fit pipeline can run without leaving Rust.
+
+use nalgebra::{DMatrix, DVector};
+
+use crate::{Centroid, DistortionBasis, MavDACError, Result, Vec2D};
+
+/// Fit `basis`'s coefficients to the given `centroids` by regularized
+/// (ridge) least-squares, and return the per-axis residual RMS
+/// `(rms_x, rms_y)`.
+///
+/// The design matrix `A` is shared between the x and y axes, with
+/// `A[i][j] = basis.sample(&centroids[i].pos, j)`, and the right-hand
+/// sides are `bx[i] = cog.x - pos.x`, `by[i] = cog.y - pos.y`. The two
+/// systems are solved via the normal equations `(A^T A + lambda*I) c = A^T b`
+/// with a Cholesky factorization, `lambda` being a ridge parameter to keep
+/// the system well-posed when the design matrix is rank-deficient.
+///
+/// Returns [`MavDACError::Fit`] if `A^T A + lambda*I` isn't positive-definite
+/// (e.g. fewer `centroids` than `basis` has coefficients, duplicate or
+/// collinear centroids, or `lambda` too small to compensate) rather than
+/// panicking, since this is reachable from ordinary (if ill-posed) caller
+/// input, not just a programming error.
+///
+/// This is only reachable from Rust: each [`crate::basis`] type exposes it
+/// to Python via its own `#[pymethods] fn fit`, which lets `basis` stay a
+/// `&mut self` receiver (the idiomatic PyO3 shape) instead of needing a
+/// separate `#[pyfunction]` dispatching over every concrete basis type.
+pub fn fit_distortions<B: DistortionBasis + ?Sized>(
+    basis: &mut B, centroids: &[Centroid], lambda: f64,
+) -> Result<(f64, f64)> {
+    let ncoeffs = basis.get_coeffs().len();
+    let npts = centroids.len();
+
+    if npts == 0 {
+        return Err(MavDACError::Fit(
+            "cannot fit distortions to zero centroids".to_string()
+        ));
+    }
+
+    let mut a = DMatrix::<f64>::zeros(npts, ncoeffs);
+    let mut bx = DVector::<f64>::zeros(npts);
+    let mut by = DVector::<f64>::zeros(npts);
+    for (i, centroid) in centroids.iter().enumerate() {
+        for j in 0..ncoeffs {
+            a[(i,j)] = basis.sample(&centroid.pos, j);
+        }
+        bx[i] = centroid.cog.x - centroid.pos.x;
+        by[i] = centroid.cog.y - centroid.pos.y;
+    }
+
+    let ata = a.transpose() * &a;
+    let lhs = ata + DMatrix::<f64>::identity(ncoeffs, ncoeffs) * lambda;
+    let chol = lhs.cholesky().ok_or_else(|| MavDACError::Fit(format!(
+        "design matrix is singular even after ridge regularization \
+        ({npts} centroids, {ncoeffs} coefficients, lambda={lambda}); try a larger lambda \
+        or more/less-degenerate centroids"
+    )))?;
+    let cx = chol.solve(&(a.transpose() * &bx));
+    let cy = chol.solve(&(a.transpose() * &by));
+
+    let coeffs = (0..ncoeffs).map(|j| Vec2D{x: cx[j], y: cy[j]}).collect();
+    basis.set_coeffs(coeffs);
+
+    let rms_x = ((&a * &cx - &bx).norm_squared() / npts as f64).sqrt();
+    let rms_y = ((&a * &cy - &by).norm_squared() / npts as f64).sqrt();
+    Ok((rms_x, rms_y))
+}
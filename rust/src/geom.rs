@@ -4,7 +4,7 @@ use std::io::Write;
 use pyo3::{pyclass, pymethods};
 use rustfft::num_traits::Float;
 use serde::{Serialize,Deserialize};
-use crate::Result;
+use crate::{MavDACError, Result};
 
 /// 2D vector, corresponding to float-valued pixel positions
 #[derive(Clone,Debug,Copy,Deserialize,PartialEq,Serialize)]
@@ -28,6 +28,38 @@ impl Vec2D {
     fn y(&self) -> f64 {
         self.y
     }
+    /// dot product with another vector
+    pub fn dot(&self, other: Vec2D) -> f64 {
+        self.x*other.x + self.y*other.y
+    }
+    /// squared length of vector (cheaper than [`Vec2D::length`])
+    pub fn length_squared(&self) -> f64 {
+        self.dot(*self)
+    }
+    /// length (euclidean norm) of vector
+    pub fn length(&self) -> f64 {
+        self.length_squared().sqrt()
+    }
+    /// unit vector in the same direction as this vector
+    pub fn normalize(&self) -> Vec2D {
+        *self * (1.0/self.length())
+    }
+    /// euclidean distance to another vector
+    pub fn distance(&self, other: Vec2D) -> f64 {
+        (*self-other).length()
+    }
+    /// rotate this vector by `angle` radians (counter-clockwise)
+    pub fn rotate(&self, angle: f64) -> Vec2D {
+        Vec2D {
+            x: self.x*angle.cos() - self.y*angle.sin(),
+            y: self.x*angle.sin() + self.y*angle.cos(),
+        }
+    }
+    /// linearly interpolate between this vector and `other`, at parameter `t`
+    /// (`t=0` returns `self`, `t=1` returns `other`)
+    pub fn lerp(&self, other: Vec2D, t: f64) -> Vec2D {
+        *self + (other-*self)*t
+    }
 }
 
 impl AddAssign for Vec2D {
@@ -65,6 +97,90 @@ impl Mul<f64> for Vec2D {
     type Output = Self;
 }
 
+/// 2D affine transform, composed of a linear part (2x2 matrix) and a
+/// translation, used to chain rotations/scales/shears applied to pinhole
+/// grids and other pixel-space geometry.
+#[derive(Clone,Copy,Debug,Serialize,Deserialize,PartialEq)]
+#[pyclass]
+pub struct Affine2 {
+    /// linear (2x2) part of the transform, row-major
+    pub matrix: [[f64;2];2],
+    /// translation part of the transform
+    pub translation: Vec2D,
+}
+
+#[pymethods]
+impl Affine2 {
+    #[new]
+    pub fn new(matrix: [[f64;2];2], translation: Vec2D) -> Affine2 {
+        Affine2{matrix, translation}
+    }
+    /// identity transform
+    #[staticmethod]
+    pub fn identity() -> Affine2 {
+        Affine2{matrix: [[1.0,0.0],[0.0,1.0]], translation: Vec2D{x:0.0,y:0.0}}
+    }
+    /// pure rotation by `angle` radians (counter-clockwise)
+    #[staticmethod]
+    pub fn from_rotation(angle: f64) -> Affine2 {
+        Affine2{
+            matrix: [
+                [angle.cos(), -angle.sin()],
+                [angle.sin(), angle.cos()],
+            ],
+            translation: Vec2D{x:0.0,y:0.0},
+        }
+    }
+    /// pure (anisotropic) scale
+    #[staticmethod]
+    pub fn from_scale(sx: f64, sy: f64) -> Affine2 {
+        Affine2{
+            matrix: [[sx,0.0],[0.0,sy]],
+            translation: Vec2D{x:0.0,y:0.0},
+        }
+    }
+    /// pure shear, `shx` shears x along y, `shy` shears y along x
+    #[staticmethod]
+    pub fn from_shear(shx: f64, shy: f64) -> Affine2 {
+        Affine2{
+            matrix: [[1.0,shx],[shy,1.0]],
+            translation: Vec2D{x:0.0,y:0.0},
+        }
+    }
+    /// pure translation
+    #[staticmethod]
+    pub fn from_translation(translation: Vec2D) -> Affine2 {
+        Affine2{matrix: [[1.0,0.0],[0.0,1.0]], translation}
+    }
+    /// apply the transform (linear part + translation) to a point
+    pub fn transform_point(&self, p: Vec2D) -> Vec2D {
+        self.transform_vector(p) + self.translation
+    }
+    /// apply just the linear part of the transform to a (direction) vector
+    pub fn transform_vector(&self, v: Vec2D) -> Vec2D {
+        Vec2D {
+            x: self.matrix[0][0]*v.x + self.matrix[0][1]*v.y,
+            y: self.matrix[1][0]*v.x + self.matrix[1][1]*v.y,
+        }
+    }
+}
+
+impl Mul for Affine2 {
+    type Output = Affine2;
+    /// compose two transforms, such that `(a*b).transform_point(p) ==
+    /// a.transform_point(b.transform_point(p))`
+    fn mul(self, rhs: Self) -> Self::Output {
+        let m = &self.matrix;
+        let n = &rhs.matrix;
+        let matrix = [
+            [m[0][0]*n[0][0]+m[0][1]*n[1][0], m[0][0]*n[0][1]+m[0][1]*n[1][1]],
+            [m[1][0]*n[0][0]+m[1][1]*n[1][0], m[1][0]*n[0][1]+m[1][1]*n[1][1]],
+        ];
+        let translation = self.transform_vector(rhs.translation) + self.translation;
+        Affine2{matrix, translation}
+    }
+}
+
 /// Grid type, defined from minimal parameters but able to determine all possible
 /// pinhole positions.
 #[pyclass]
@@ -78,15 +194,27 @@ pub enum Grid {
         rotation: f64,  // radians
         /// offset of pinhole grid (if {0,0} then there is a pinhole at the centre of the image)
         offset: Vec2D,  // pixels
+        /// anisotropic scale applied to the grid before rotation, to model
+        /// non-square detector pixels ({1.0,1.0} for square pixels)
+        #[serde(default = "default_scale")]
+        scale: Vec2D,
+        /// shear applied to the grid before rotation, to model a slightly
+        /// sheared pinhole mask (0.0 for no shear)
+        #[serde(default)]
+        shear: f64,
     },
 }
 
+fn default_scale() -> Vec2D {
+    Vec2D{x: 1.0, y: 1.0}
+}
+
 impl Add<Vec2D> for Grid {
     fn add(self, rhs: Vec2D) -> Self::Output {
         match self {
-            Grid::Hex { pitch, rotation, offset } => 
-                Grid::Hex { pitch, rotation, offset: offset + rhs },
-        }   
+            Grid::Hex { pitch, rotation, offset, scale, shear } =>
+                Grid::Hex { pitch, rotation, offset: offset + rhs, scale, shear },
+        }
     }
     type Output = Self;
 }
@@ -97,8 +225,9 @@ impl Grid {
     /// load grid from yaml file
     #[new]
     pub fn from_yaml(filename: &str) -> Result<Grid> {
-        let f = std::fs::File::open(filename)?;
-        let grid: Grid = serde_yaml::from_reader(f)?;
+        let contents = std::fs::read_to_string(filename)?;
+        let grid: Grid = serde_yaml::from_str(&contents)
+            .map_err(|e| MavDACError::from(e).with_path(filename).with_snippet(&contents))?;
         Ok(grid)
     }
     /// save grid to yaml file
@@ -111,7 +240,12 @@ impl Grid {
     pub fn all_points(&self, width: usize, height: usize) -> Vec<Vec2D> {
         let max_rad = width.max(height)*4;
         match self {
-            Grid::Hex { pitch, rotation, offset } => {
+            Grid::Hex { pitch, rotation, offset, scale, shear } => {
+                // rotation/anisotropic-scale/shear of the grid, applied about
+                // the origin before translating to the image centre+offset
+                let transform = Affine2::from_rotation(*rotation)
+                    * Affine2::from_shear(*shear, 0.0)
+                    * Affine2::from_scale(scale.x, scale.y);
                 // first make a square grid with way too many points
                 (0..2*max_rad).map(|x| x as f64)
                 .flat_map(|x| (0..2*max_rad)
@@ -124,11 +258,11 @@ impl Grid {
                 .map(|(x,y)| (x*pitch, y*pitch))
                 // then map it to a hex grid with gaps
                 .map(|(x,y)| (x+0.5*y,y*(3.0).sqrt()/2.0))
-                // rotate
-                .map(|(x,y)| (
-                    x*rotation.cos()-y*rotation.sin(),
-                    x*rotation.sin()+y*rotation.cos(),
-                ))
+                // apply rotation/scale/shear
+                .map(|(x,y)| {
+                    let p = transform.transform_point(Vec2D{x,y});
+                    (p.x, p.y)
+                })
                 // now apply the offset:
                 .map(|(x,y)| (x+offset.x, y+offset.y))
                 // shift back to valid pixel range
@@ -148,6 +282,9 @@ pub struct Centroid {
     pub cog: Vec2D,
     pub flux: f64,
     pub pos: Vec2D,
+    /// local background level subtracted before flux-weighting (0.0 if no
+    /// background subtraction was requested)
+    pub bg: f64,
 }
 
 #[pymethods]
@@ -172,9 +309,15 @@ impl Centroid {
     pub fn posy(&self) -> f64 {
         self.pos.y
     }
-    /// flux of centroid (summed over all valid pixels)
+    /// flux of centroid (summed over all valid pixels, after background
+    /// subtraction)
     #[getter]
     pub fn flux(&self) -> f64 {
         self.flux
     }
+    /// local background level subtracted before flux-weighting
+    #[getter]
+    pub fn bg(&self) -> f64 {
+        self.bg
+    }
 }
\ No newline at end of file
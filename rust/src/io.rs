@@ -3,7 +3,49 @@ use std::{f64::consts::PI, fmt::Display};
 use fitrs::{Fits, FitsData, Hdu, HeaderValue};
 use pyo3::{pyclass, pymethods};
 
-use crate::{Centroid, Grid, MavDACError, Result, Vec2D};
+use crate::errors::Diagnostic;
+use crate::{ops, Centroid, Grid, MavDACError, Result, Vec2D};
+
+/// read the 80-byte header cards of `filename`'s primary HDU, up to and
+/// including the `END` card, for use in diagnostics (best-effort: returns
+/// an empty vec if the file can't be read)
+fn header_cards(filename: &str) -> Vec<String> {
+    let Ok(data) = std::fs::read(filename) else {
+        return vec![];
+    };
+    let mut cards = vec![];
+    for chunk in data.chunks(80) {
+        if chunk.len() < 80 {
+            break;
+        }
+        let card = String::from_utf8_lossy(chunk).to_string();
+        let is_end = card.trim_start().starts_with("END");
+        cards.push(card);
+        if is_end {
+            break;
+        }
+    }
+    cards
+}
+
+/// locate the (1-indexed) header card for `keyword`, if present
+fn locate_card(cards: &[String], keyword: &str) -> Option<usize> {
+    cards.iter().position(|card|
+        card.split('=').next().unwrap_or("").trim() == keyword
+    ).map(|i| i+1)
+}
+
+/// build an [`MavDACError::InvalidFITS`] diagnostic pointing at the header
+/// card for `keyword` in `filename`, if it can be located
+fn invalid_fits(filename: &str, keyword: &str, message: String) -> MavDACError {
+    let cards = header_cards(filename);
+    let mut diagnostic = Diagnostic::new(message).with_path(filename);
+    if let Some(line) = locate_card(&cards, keyword) {
+        diagnostic = diagnostic.with_location(line, 1)
+            .with_snippet(cards[line-1].trim_end().to_string());
+    }
+    MavDACError::InvalidFITS(diagnostic)
+}
 
 
 #[derive(Debug, Clone)]
@@ -38,38 +80,40 @@ impl Image {
         if let Some(hdu) = fits.get(0) {
             match hdu.value("NAXIS")  {
                 Some(HeaderValue::IntegerNumber(2)) => (),
-                _ => return Err(MavDACError::InvalidFITS("expected NAXIS==2".to_string())),
+                _ => return Err(invalid_fits(filename, "NAXIS", "expected NAXIS==2".to_string())),
             };
             let mut shape: [usize;2] = [0,0];
             match hdu.value("NAXIS2")  {
                 Some(HeaderValue::IntegerNumber(x)) if *x > 0 => {
                     shape[0] = *x as usize;
                 },
-                _ => return Err(MavDACError::InvalidFITS("invalid NAXIS2".to_string())),
+                _ => return Err(invalid_fits(filename, "NAXIS2", "invalid NAXIS2".to_string())),
             }
             match hdu.value("NAXIS1")  {
                 Some(HeaderValue::IntegerNumber(x)) if *x > 0 => {
                     shape[1] = *x as usize;
                 },
-                _ => return Err(MavDACError::InvalidFITS("invalid NAXIS1".to_string())),
+                _ => return Err(invalid_fits(filename, "NAXIS1", "invalid NAXIS1".to_string())),
             };
             let shift = {
-                let xshift: f64 = match hdu.value("XSHIFT").ok_or(MavDACError::InvalidFITS(
-                    format!("missing XSHIFT in fits header {}", filename)
+                let xshift: f64 = match hdu.value("XSHIFT").ok_or_else(|| invalid_fits(
+                    filename, "XSHIFT", format!("missing XSHIFT in fits header {}", filename)
                 ))? {
                     HeaderValue::IntegerNumber(a) => *a as f64,
                     HeaderValue::RealFloatingNumber(a) => *a,
-                    _ => return Err(MavDACError::InvalidFITS(
+                    _ => return Err(invalid_fits(
+                        filename, "XSHIFT",
                         format!("XSHIFT in fits header has invalid datatype, \
                                 must be float or int {}", filename)
                     ))
                 };
-                let yshift: f64 = match hdu.value("YSHIFT").ok_or(MavDACError::InvalidFITS(
-                    format!("missing YSHIFT in fits header {}", filename)
+                let yshift: f64 = match hdu.value("YSHIFT").ok_or_else(|| invalid_fits(
+                    filename, "YSHIFT", format!("missing YSHIFT in fits header {}", filename)
                 ))? {
                     HeaderValue::IntegerNumber(a) => *a as f64,
                     HeaderValue::RealFloatingNumber(a) => *a,
-                    _ => return Err(MavDACError::InvalidFITS(
+                    _ => return Err(invalid_fits(
+                        filename, "YSHIFT",
                         format!("YSHIFT in fits header has invalid datatype, \
                                 must be float or int\n{}", filename)
                     ))
@@ -98,11 +142,10 @@ impl Image {
             };
             Ok(Image { data, shape, shift })
         } else {
-            Err (
-                MavDACError::InvalidFITS(
-                    format!("no primary hdu in {}", &filename)
-                )
-            )
+            Err(MavDACError::InvalidFITS(
+                Diagnostic::new(format!("no primary hdu in {}", &filename))
+                    .with_path(filename)
+            ))
         }
     }
 
@@ -123,7 +166,7 @@ impl Image {
         .flat_map(|v| {
             (0..1000).map(|i| i as f64 / NTHETA as f64)
             .map(|t| t*2.0*PI)
-            .map(move |theta| (v.x + theta.cos()*rad, v.y + theta.sin()*rad))
+            .map(move |theta| (v.x + ops::cos(theta)*rad, v.y + ops::sin(theta)*rad))
             .map(|(x,y)| (x as usize, y as usize))
             .filter(|(x,y)| *x < self.shape[1] && *y < self.shape[0])
         })
@@ -133,26 +176,75 @@ impl Image {
     }
 
     /// compute centroids for image given a grid and cog-radius
-    pub fn cogs(&self, grid: &Grid, rad: usize) -> Vec<Centroid> {
+    ///
+    /// `max_iter`/`tol` control the iterative recentering of the windowed
+    /// COG (see [`Image::cog`]), and `bg_annulus` is the width (in pixels)
+    /// of the annulus just outside `rad` used to estimate a local
+    /// background (`0.0` disables background subtraction).
+    pub fn cogs(
+        &self, grid: &Grid, rad: usize, max_iter: usize, tol: f64, bg_annulus: f64
+    ) -> Vec<Centroid> {
         // get all nominal positions
         let points = grid.all_points(self.shape[1], self.shape[0]);
 
         // measure cog and intensity within radius at all points
-        points.into_iter().map(|v| v+self.shift).map(|point| self.cog(&point, rad)).collect()
+        points.into_iter().map(|v| v+self.shift)
+        .map(|point| self.cog(&point, rad, max_iter, tol, bg_annulus))
+        .collect()
     }
 
     /// compute centroid for image given a point and cog-radius
-    pub fn cog(&self, point: &Vec2D, rad: usize) -> Centroid {
-        let (sumx,sumy,flux) = self.get_blob(&point.clone(), rad).into_iter()
-        .map(|pixel| (
-            pixel.x as f64*pixel.val,
-            pixel.y as f64*pixel.val,
-            pixel.val
-        )).fold((0.0,0.0,0.0), |a,b| (a.0+b.0, a.1+b.1, a.2+b.2));
+    ///
+    /// The window is recentered on the windowed COG and re-measured, up to
+    /// `max_iter` times or until the shift between iterations drops below
+    /// `tol`. If `bg_annulus > 0.0`, a local background (the median of an
+    /// annulus of that width just outside `rad`) is subtracted before
+    /// flux-weighting on every iteration.
+    pub fn cog(
+        &self, point: &Vec2D, rad: usize, max_iter: usize, tol: f64, bg_annulus: f64
+    ) -> Centroid {
+        let mut center = *point;
+        let mut flux = 0.0;
+        let mut bg = 0.0;
+        for _ in 0..max_iter.max(1) {
+            bg = if bg_annulus > 0.0 {
+                self.local_background(&center, rad, bg_annulus)
+            } else {
+                0.0
+            };
+            let (sumx,sumy,sumv) = self.get_blob(&center, rad).into_iter()
+            .map(|pixel| {
+                let v = (pixel.val - bg).max(0.0);
+                (pixel.x as f64*v, pixel.y as f64*v, v)
+            }).fold((0.0,0.0,0.0), |a,b| (a.0+b.0, a.1+b.1, a.2+b.2));
+            flux = sumv;
+            let new_center = if sumv > 0.0 {
+                Vec2D{x: sumx/sumv, y: sumy/sumv}
+            } else {
+                center
+            };
+            let shift = new_center.distance(center);
+            center = new_center;
+            if shift < tol {
+                break;
+            }
+        }
+        // the flux/bg above were measured at the window *before* the
+        // final recenter, so recompute them at the position `cog` below
+        // actually reports, rather than the one-iteration-stale window
+        bg = if bg_annulus > 0.0 {
+            self.local_background(&center, rad, bg_annulus)
+        } else {
+            0.0
+        };
+        flux = self.get_blob(&center, rad).into_iter()
+            .map(|pixel| (pixel.val - bg).max(0.0))
+            .sum();
         Centroid {
-            cog: Vec2D{x: sumx / flux, y: sumy /flux},
+            cog: center,
             flux,
             pos: Vec2D{x: point.x, y: point.y},
+            bg,
         }
     }
 
@@ -188,6 +280,46 @@ impl Image {
         }
         pixels
     }
+
+    /// estimate the local background as the median pixel value in an
+    /// annulus of width `bg_width` just outside `rad`, centered on `pos`
+    fn local_background(&self, pos: &Vec2D, rad: usize, bg_width: f64) -> f64 {
+        let rad = rad as isize;
+        let rad_outer = (rad as f64 + bg_width).ceil() as isize;
+        let xc = pos.x as isize;
+        let yc = pos.y as isize;
+        let mut values: Vec<f64> = vec![];
+        for x in -rad_outer..rad_outer+1 {
+            for y in -rad_outer..rad_outer+1 {
+                let r2 = x.pow(2) + y.pow(2);
+                if r2 <= rad.pow(2) || r2 as f64 > (rad as f64 + bg_width).powi(2) {
+                    continue;
+                }
+                let px = (x + xc) as usize;
+                let py = (y + yc) as usize;
+                if px >= self.shape[1] || py >= self.shape[0] {
+                    continue;
+                }
+                values.push(self.data[py*self.shape[1]+px]);
+            }
+        }
+        median(values)
+    }
+}
+
+/// median of a set of values (`0.0` for an empty set)
+fn median(mut values: Vec<f64>) -> f64 {
+    values.retain(|v| !v.is_nan());
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    let n = values.len();
+    if n % 2 == 1 {
+        values[n/2]
+    } else {
+        0.5*(values[n/2-1]+values[n/2])
+    }
 }
     
 
@@ -11,16 +11,21 @@
 //! let grid = Grid::Hex {
 //!     pitch: 100.0,  // pixels
 //!     rotation: 0.0,  // radians
-//!     offset: Vec2D{x:0.0,y:0.0}  // pixels
+//!     offset: Vec2D{x:0.0,y:0.0},  // pixels
+//!     scale: Vec2D{x:1.0,y:1.0},  // anisotropic scale (1.0 for square pixels)
+//!     shear: 0.0,  // shear
 //! };
 //! let cogs = mavdac::measure_cogs(
 //!     imgs,  // vector of images
 //!     grid,  // grid geometry
 //!     10,  // radius for centroider
 //!     10_000.0,  // flux threshold for "valid" cogs
+//!     10,  // max iterations for windowed recentering
+//!     1e-3,  // convergence tolerance (pixels)
+//!     0.0,  // annulus width for background estimation (0.0 to disable)
 //! );
-//! // all of the remaining tasks are done in python, since numpy.linalg is more
-//! // reliable than any rust linalg solution I tried. 
+//! // fitting distortion coefficients to these cogs is left to the caller,
+//! // e.g. via `BiVarPolyDistortions::fit`.
 //! ```
 use pyo3::prelude::*;
 use core::f64;
@@ -31,24 +36,45 @@ mod errors;
 mod basis;
 mod io;
 mod geom;
+mod ops;
+mod fit;
+mod patterns;
+mod batch;
 pub use crate::io::{Image, Coordinate};
-pub use crate::errors::{MavDACError, Result};
-pub use crate::geom::{Centroid,Vec2D,Grid};
-pub use crate::basis::{BiVarPolyDistortions,BiVarFourierDistortions,DistortionBasis};
+pub use crate::errors::{
+    BadPatternError, ConfigError, CoordinateError, Diagnostic, FitError, InvalidFitsError,
+    MavDACError, MavdacError, PatternError, Result, UnreadablePathError,
+};
+pub use crate::geom::{Centroid,Vec2D,Grid,Affine2};
+pub use crate::basis::{BiVarPolyDistortions,BiVarFourierDistortions,BiVarZernikeDistortions,DistortionBasis};
+pub use crate::fit::fit_distortions;
+pub use crate::patterns::{Pattern, PatternSet};
+pub use crate::batch::BadMatch;
 
 /// A Python module implemented in Rust.
 #[pymodule]
 fn mavdac(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(get_coordinates, m)?)?;
     m.add_function(wrap_pyfunction!(load_images, m)?)?;
+    m.add_function(wrap_pyfunction!(load_images_checked, m)?)?;
     m.add_function(wrap_pyfunction!(measure_cogs, m)?)?;
     m.add_class::<Image>()?;
     m.add_class::<Grid>()?;
     m.add_class::<Centroid>()?;
     m.add_class::<BiVarPolyDistortions>()?;
     m.add_class::<BiVarFourierDistortions>()?;
+    m.add_class::<BiVarZernikeDistortions>()?;
     m.add_class::<Coordinate>()?;
     m.add_class::<Vec2D>()?;
+    m.add_class::<Affine2>()?;
+    m.add_class::<BadMatch>()?;
+    m.add("MavdacError", m.py().get_type::<MavdacError>())?;
+    m.add("BadPatternError", m.py().get_type::<BadPatternError>())?;
+    m.add("UnreadablePathError", m.py().get_type::<UnreadablePathError>())?;
+    m.add("InvalidFitsError", m.py().get_type::<InvalidFitsError>())?;
+    m.add("CoordinateError", m.py().get_type::<CoordinateError>())?;
+    m.add("ConfigError", m.py().get_type::<ConfigError>())?;
+    m.add("FitError", m.py().get_type::<FitError>())?;
     Ok(())
 }
 
@@ -64,20 +90,38 @@ pub fn load_images(pattern: &str) -> Result<Vec<Image>> {
     .collect::<Result<Vec<Image>>>()
 }
 
+/// Load images found under `root` that match `patterns` (see
+/// [`crate::patterns`] for the supported `glob:`/`re:`/`rootglob:`/`path:`/
+/// `exclude:` syntaxes), tolerating individual files that fail to load.
+///
+/// Returns the successfully-loaded images alongside a report of every
+/// selected file that could not be loaded and why, instead of aborting the
+/// whole scan on the first bad FITS file.
+#[pyfunction]
+pub fn load_images_checked(root: &str, patterns: Vec<String>) -> Result<(Vec<Image>, Vec<BadMatch>)> {
+    batch::load_images_checked(root, &patterns)
+}
+
 /// measured centroids from a set of images
+///
+/// `max_iter`/`tol` control the iterative recentering of the windowed COG
+/// (see [`Image::cog`]), and `bg_annulus` is the width (in pixels) of the
+/// annulus just outside `rad` used to estimate a local background (`0.0`
+/// disables background subtraction).
 #[pyfunction]
 pub fn measure_cogs(
-    images: Vec<Image>, grid: Grid, rad: usize, fluxthresh: f64
+    images: Vec<Image>, grid: Grid, rad: usize, fluxthresh: f64,
+    max_iter: usize, tol: f64, bg_annulus: f64,
 ) -> Vec<Vec<Centroid>> {
     if images.is_empty() {
         return vec![];
     }
     let pinholes = grid.all_points(images[0].shape[1], images[0].shape[0]);
-    
-    // cogs should be a n_pinholes x n_images 
+
+    // cogs should be a n_pinholes x n_images
     let mut cogs: Vec<Option<Vec<Centroid>>> = pinholes.par_iter().map(|pinhole|
         images.iter().map(|image|
-            image.cog(&(*pinhole+image.shift), rad)
+            image.cog(&(*pinhole+image.shift), rad, max_iter, tol, bg_annulus)
         ).collect::<Vec<Centroid>>()
     ).map(|pinhole_cogs|
         if pinhole_cogs.iter().all(|cog|
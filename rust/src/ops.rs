@@ -0,0 +1,54 @@
+//! Deterministic transcendental math.
+//!
+//! The basis functions and `Image::draw_on_circles` rely on `sin`/`cos`/`powf`, whose
+//! precision is platform/compiler-unspecified, so the same inputs can yield
+//! slightly different distortion coefficients on different machines or Rust
+//! versions. Following [bevy_math](https://docs.rs/bevy_math)'s approach,
+//! this module re-exports either the `std` float methods or their `libm`
+//! equivalents, selected by the `libm` cargo feature, so that callers get
+//! bit-reproducible results across platforms when the feature is enabled.
+
+#[cfg(not(feature = "libm"))]
+pub fn sin(x: f64) -> f64 {
+    x.sin()
+}
+#[cfg(feature = "libm")]
+pub fn sin(x: f64) -> f64 {
+    libm::sin(x)
+}
+
+#[cfg(not(feature = "libm"))]
+pub fn cos(x: f64) -> f64 {
+    x.cos()
+}
+#[cfg(feature = "libm")]
+pub fn cos(x: f64) -> f64 {
+    libm::cos(x)
+}
+
+#[cfg(not(feature = "libm"))]
+pub fn pow(x: f64, y: f64) -> f64 {
+    x.powf(y)
+}
+#[cfg(feature = "libm")]
+pub fn pow(x: f64, y: f64) -> f64 {
+    libm::pow(x, y)
+}
+
+#[cfg(not(feature = "libm"))]
+pub fn atan2(y: f64, x: f64) -> f64 {
+    y.atan2(x)
+}
+#[cfg(feature = "libm")]
+pub fn atan2(y: f64, x: f64) -> f64 {
+    libm::atan2(y, x)
+}
+
+#[cfg(not(feature = "libm"))]
+pub fn sqrt(x: f64) -> f64 {
+    x.sqrt()
+}
+#[cfg(feature = "libm")]
+pub fn sqrt(x: f64) -> f64 {
+    libm::sqrt(x)
+}
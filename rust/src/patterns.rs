@@ -0,0 +1,180 @@
+//! Image-selection patterns.
+//!
+//! A single `glob` pattern is too blunt a tool for selecting calibration
+//! frames out of a directory tree: sometimes a plain glob is easiest,
+//! sometimes a full regular expression is needed, and sometimes a user just
+//! wants "everything under this directory" or "everything matching this
+//! glob, but not that one". This module compiles a small set of prefixed
+//! pattern syntaxes down to a single [`regex::Regex`] each, so a
+//! [`PatternSet`] of include/exclude patterns can be applied uniformly to
+//! candidate paths.
+//!
+//! Supported prefixes:
+//! - `glob:<pattern>` - a shell-style glob, matched against the tail of the
+//!   path (an implicit `**/` is allowed before it), e.g. `glob:*.fits`
+//!   matches `a.fits` and `a/b/c.fits` alike.
+//! - `rootglob:<pattern>` - the same glob translation, but anchored at the
+//!   search root: the pattern must describe the whole relative path (use an
+//!   explicit `**/` to allow arbitrary intermediate directories).
+//! - `re:<pattern>` - an arbitrary regular expression, passed straight to
+//!   [`regex::Regex`], unanchored.
+//! - `path:<prefix>` - a literal directory prefix, anchored at the start of
+//!   the path.
+//!
+//! An `exclude:` prefix (applied in front of any of the above, e.g.
+//! `exclude:glob:*.bad.fits`) moves a pattern from the include set to the
+//! exclude set of a [`PatternSet`]. A candidate path is selected iff it
+//! matches at least one include pattern and no exclude pattern.
+
+use regex::Regex;
+
+use crate::errors::{MavDACError, PatternError};
+use crate::Result;
+
+/// glob metacharacters (beyond `*`/`**`/`?`, which are handled specially)
+/// that must be escaped when translated to a regex
+const REGEX_METACHARS: &str = "()[]{}?*+-|^$\\.&~#";
+
+/// translate a shell-style glob into the body of a regex (no anchors)
+fn glob_to_regex_body(pattern: &str) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '*' && chars.get(i+1) == Some(&'*') && chars.get(i+2) == Some(&'/') {
+            out.push_str("(?:.*/)?");
+            i += 3;
+            continue;
+        }
+        match chars[i] {
+            '*' => out.push_str("[^/]*"),
+            '?' => out.push_str("[^/]"),
+            c if REGEX_METACHARS.contains(c) || c.is_whitespace() => {
+                out.push('\\');
+                out.push(c);
+            },
+            c => out.push(c),
+        }
+        i += 1;
+    }
+    out
+}
+
+/// best-effort byte offset of the span a `regex::Error` complains about,
+/// recovered from the caret (`^`) regex-syntax draws under its rendered
+/// error snippet
+///
+/// This offset is into the *translated* regex body handed to
+/// [`regex::Regex::new`] (post prefix-stripping, and for `glob:`/
+/// `rootglob:` also post glob-to-regex substitution and metachar
+/// escaping), not into [`PatternError::pattern`], which holds the
+/// original prefixed string the user typed. For a `re:` pattern it is an
+/// offset into `pattern` past its 3-byte `re:` prefix; for `glob:`/
+/// `rootglob:`/bare patterns the body is a structurally different string
+/// than `pattern`, so don't use it to index into `pattern` for those.
+fn error_offset(message: &str) -> usize {
+    // regex-syntax indents both the echoed pattern and the caret line
+    // beneath it by four spaces; the caret line is the one holding only
+    // carets and spaces (the echoed pattern may itself contain a `^`)
+    const INDENT: usize = 4;
+    message.lines()
+        .filter(|line| line.starts_with("    "))
+        .find(|line| line.contains('^') && line.chars().all(|c| c == '^' || c == ' '))
+        .and_then(|line| line.find('^'))
+        .map(|col| col - INDENT)
+        .unwrap_or(0)
+}
+
+fn compile(full_pattern: &str, body: String) -> Result<Regex> {
+    Regex::new(&body).map_err(|e| {
+        let message = e.to_string();
+        let offset = error_offset(&message);
+        MavDACError::Pattern(PatternError {
+            pattern: full_pattern.to_string(),
+            offset,
+            message,
+        })
+    })
+}
+
+/// a single compiled include/exclude pattern
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    regex: Regex,
+}
+
+impl Pattern {
+    /// parse one prefixed pattern string (without a leading `exclude:`)
+    pub fn parse(spec: &str) -> Result<Pattern> {
+        let regex = if let Some(rest) = spec.strip_prefix("glob:") {
+            // anchored overall; the leading `(?:.*/)?` is what allows
+            // arbitrary directories before the glob body, not the lack of
+            // a `^` (an unanchored regex would let `(?:.*/)?` match a
+            // substring of an unrelated path component)
+            compile(spec, format!("^(?:.*/)?{}$", glob_to_regex_body(rest)))?
+        } else if let Some(rest) = spec.strip_prefix("rootglob:") {
+            compile(spec, format!("^{}$", glob_to_regex_body(rest)))?
+        } else if let Some(rest) = spec.strip_prefix("re:") {
+            compile(spec, rest.to_string())?
+        } else if let Some(rest) = spec.strip_prefix("path:") {
+            // anchored on a path-component boundary at both ends, so
+            // `path:data/cal` matches `data/cal` and `data/cal/x.fits` but
+            // not a sibling like `data/calibration_bad/x.fits`
+            compile(spec, format!("^{}(?:/|$)", regex::escape(rest.trim_end_matches('/'))))?
+        } else {
+            // bare patterns (no prefix) are treated as glob:, so they match
+            // the tail of the path at any depth
+            compile(spec, format!("^(?:.*/)?{}$", glob_to_regex_body(spec)))?
+        };
+        Ok(Pattern{regex})
+    }
+
+    /// does `path` match this pattern?
+    pub fn is_match(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+}
+
+/// a set of include and exclude patterns, as parsed from a list of
+/// (possibly `exclude:`-prefixed) pattern strings
+#[derive(Debug, Clone)]
+pub struct PatternSet {
+    includes: Vec<Pattern>,
+    excludes: Vec<Pattern>,
+}
+
+impl PatternSet {
+    /// parse a list of pattern strings into a `PatternSet`
+    pub fn parse(specs: &[String]) -> Result<PatternSet> {
+        let mut includes = vec![];
+        let mut excludes = vec![];
+        for spec in specs {
+            if let Some(rest) = spec.strip_prefix("exclude:") {
+                excludes.push(Pattern::parse(rest)?);
+            } else {
+                includes.push(Pattern::parse(spec)?);
+            }
+        }
+        Ok(PatternSet{includes, excludes})
+    }
+
+    /// does `path` match at least one include pattern, and no exclude
+    /// pattern?
+    pub fn matches(&self, path: &str) -> bool {
+        self.includes.iter().any(|p| p.is_match(path))
+            && !self.excludes.iter().any(|p| p.is_match(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pattern;
+
+    #[test]
+    fn path_prefix_does_not_match_sibling_directory() {
+        let pattern = Pattern::parse("path:data/cal").unwrap();
+        assert!(pattern.is_match("data/cal/x.fits"));
+        assert!(pattern.is_match("data/cal"));
+        assert!(!pattern.is_match("data/calibration_bad/x.fits"));
+    }
+}